@@ -0,0 +1,129 @@
+use log::{info, warn};
+
+use crate::jobs::GlobalState;
+
+// Code + metadata a signature-decode request needs to run against, along with
+// which player client it came from.
+pub struct DecodeContext {
+    pub nsig_function_code: String,
+    pub sig_function_code: String,
+    pub sig_function_name: String,
+    pub player_variant: String,
+}
+
+#[derive(Debug)]
+pub enum DecodeContextError {
+    NoPlayerAvailable,
+}
+
+// Picks which cached player's extracted code a signature-decode request
+// should run against. Requests are issued a signature_timestamp alongside the
+// player's JS, so if the client is still pinned to an older player (the live
+// one has since rotated), look that one up in the player_cache LRU instead of
+// always decoding against whatever is currently live. Falls back to the live
+// player when no signature_timestamp is given, or when it doesn't match
+// anything cached.
+pub async fn resolve_decode_context(
+    state: &GlobalState,
+    signature_timestamp: Option<u64>,
+) -> Result<DecodeContext, DecodeContextError> {
+    if let Some(signature_timestamp) = signature_timestamp {
+        if let Some(entry) =
+            crate::player::player_by_signature_timestamp(state, signature_timestamp).await
+        {
+            info!(
+                "Decoding against player variant '{}' pinned to signature_timestamp {}",
+                entry.player_variant, signature_timestamp
+            );
+            return Ok(DecodeContext {
+                nsig_function_code: entry.nsig_function_code,
+                sig_function_code: entry.sig_function_code,
+                sig_function_name: entry.sig_function_name,
+                player_variant: entry.player_variant,
+            });
+        }
+        warn!(
+            "No cached player for signature_timestamp {}, falling back to the live player",
+            signature_timestamp
+        );
+    }
+
+    let current_player_info = state.player_info.lock().await;
+    if current_player_info.has_player != 0xFF {
+        return Err(DecodeContextError::NoPlayerAvailable);
+    }
+    Ok(DecodeContext {
+        nsig_function_code: current_player_info.nsig_function_code.clone(),
+        sig_function_code: current_player_info.sig_function_code.clone(),
+        sig_function_name: current_player_info.sig_function_name.clone(),
+        player_variant: current_player_info.player_variant.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::PlayerInfoCache;
+    use std::time::SystemTime;
+
+    #[tokio::test]
+    async fn falls_back_to_live_player_when_no_timestamp_given() {
+        let state = GlobalState::default();
+        {
+            let mut player_info = state.player_info.lock().await;
+            player_info.has_player = 0xFF;
+            player_info.nsig_function_code = "function decrypt_nsig(a){return a}".to_string();
+            player_info.sig_function_code = "function decrypt_sig(a){return a}".to_string();
+            player_info.sig_function_name = "decrypt_sig".to_string();
+            player_info.player_variant = "web".to_string();
+            player_info.last_update = SystemTime::now();
+        }
+
+        let context = resolve_decode_context(&state, None)
+            .await
+            .expect("live player should be available");
+        assert_eq!(context.player_variant, "web");
+        assert_eq!(context.sig_function_name, "decrypt_sig");
+    }
+
+    #[tokio::test]
+    async fn errors_when_no_live_player_and_no_timestamp_match() {
+        let state = GlobalState::default();
+        let result = resolve_decode_context(&state, None).await;
+        assert!(matches!(result, Err(DecodeContextError::NoPlayerAvailable)));
+    }
+
+    #[tokio::test]
+    async fn prefers_player_pinned_to_an_older_signature_timestamp() {
+        let state = GlobalState::default();
+        {
+            let mut player_info = state.player_info.lock().await;
+            player_info.has_player = 0xFF;
+            player_info.nsig_function_code = "function decrypt_nsig(a){return a}".to_string();
+            player_info.sig_function_code = "function decrypt_sig(a){return a}".to_string();
+            player_info.sig_function_name = "decrypt_sig".to_string();
+            player_info.signature_timestamp = 200;
+            player_info.player_variant = "web".to_string();
+        }
+        {
+            let mut player_cache = state.player_cache.lock().await;
+            *player_cache = PlayerInfoCache::new(4);
+            player_cache.insert(
+                1,
+                crate::player::PlayerCacheEntry {
+                    nsig_function_code: "function decrypt_nsig(a){return a+1}".to_string(),
+                    sig_function_code: "function decrypt_sig(a){return a+1}".to_string(),
+                    sig_function_name: "decrypt_sig_old".to_string(),
+                    signature_timestamp: 100,
+                    player_variant: "web_embedded".to_string(),
+                },
+            );
+        }
+
+        let context = resolve_decode_context(&state, Some(100))
+            .await
+            .expect("pinned player should be found in the cache");
+        assert_eq!(context.player_variant, "web_embedded");
+        assert_eq!(context.sig_function_name, "decrypt_sig_old");
+    }
+}
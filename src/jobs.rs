@@ -0,0 +1,53 @@
+use std::time::SystemTime;
+use tokio::sync::Mutex;
+
+use crate::player::PlayerInfoCache;
+
+// Extracted player code and metadata for the currently-live player. Guarded
+// by GlobalState.player_info so concurrent fetch_update/decode tasks see a
+// consistent snapshot.
+pub struct PlayerInfo {
+    pub player_id: u32,
+    pub has_player: u8,
+    pub last_update: SystemTime,
+    pub nsig_function_code: String,
+    pub sig_function_code: String,
+    pub sig_function_name: String,
+    pub signature_timestamp: u64,
+    // Which player client variant this code came from: "web" or
+    // "web_embedded" (see PLAYER_JS_VARIANTS), or "ytdlp" in ytdlp mode.
+    // Other clients such as tv or ios are not fetched.
+    pub player_variant: String,
+}
+
+impl Default for PlayerInfo {
+    fn default() -> Self {
+        Self {
+            player_id: 0,
+            has_player: 0,
+            last_update: SystemTime::UNIX_EPOCH,
+            nsig_function_code: String::new(),
+            sig_function_code: String::new(),
+            sig_function_name: String::new(),
+            signature_timestamp: 0,
+            player_variant: String::new(),
+        }
+    }
+}
+
+pub struct GlobalState {
+    pub player_info: Mutex<PlayerInfo>,
+    // Bounded LRU of extracted code for recently-seen players, so clients
+    // pinned to an older player via a cached signature_timestamp keep working
+    // after a newer player_id goes live. See PlayerInfoCache.
+    pub player_cache: Mutex<PlayerInfoCache>,
+}
+
+impl Default for GlobalState {
+    fn default() -> Self {
+        Self {
+            player_info: Mutex::new(PlayerInfo::default()),
+            player_cache: Mutex::new(PlayerInfoCache::default()),
+        }
+    }
+}
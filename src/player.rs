@@ -1,17 +1,66 @@
-use std::{sync::Arc, time::SystemTime};
+use std::{fs, path::PathBuf, sync::Arc, time::SystemTime};
 use log::{debug, error, info, warn};
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     consts::{
         NSIG_FUNCTION_ARRAYS, NSIG_FUNCTION_ENDINGS, NSIG_FUNCTION_NAME, REGEX_HELPER_OBJ_NAME,
-        REGEX_PLAYER_ID, REGEX_SIGNATURE_FUNCTION, REGEX_SIGNATURE_TIMESTAMP, TEST_YOUTUBE_VIDEO, 
-        ENV_PLAYER_ID_FORCE, ENV_PLAYER_ID_UPDATE_DISABLED
+        REGEX_PLAYER_ID, REGEX_SIGNATURE_FUNCTION, REGEX_SIGNATURE_TIMESTAMP, TEST_YOUTUBE_VIDEO,
+        ENV_PLAYER_ID_FORCE, ENV_PLAYER_ID_UPDATE_DISABLED, ENV_PLAYER_CACHE_PATH
     },
     jobs::GlobalState,
     ytdlp::{ytdlp_requested, ytdlp_signature_timestamp},
 };
 
+// Entry persisted to the on-disk player cache, keyed by player_id.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct PlayerCacheEntry {
+    pub(crate) nsig_function_code: String,
+    pub(crate) sig_function_code: String,
+    pub(crate) sig_function_name: String,
+    pub(crate) signature_timestamp: u64,
+    pub(crate) player_variant: String,
+}
+
+fn player_cache_path() -> PathBuf {
+    PathBuf::from(
+        std::env::var(ENV_PLAYER_CACHE_PATH).unwrap_or_else(|_| "player_cache.json".to_string()),
+    )
+}
+
+// The on-disk cache is stored as the same ordered (player_id, entry) pairs as
+// the in-memory LRU and loaded back into one of the same capacity, so it can
+// never accumulate more stale players on disk than we'd ever keep in memory.
+fn load_player_cache() -> PlayerInfoCache {
+    let path = player_cache_path();
+    let entries: Vec<(u32, PlayerCacheEntry)> = match fs::read_to_string(&path) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|x| {
+            warn!("Could not parse player cache at {:?}: {}", path, x);
+            Vec::new()
+        }),
+        Err(_) => Vec::new(),
+    };
+
+    let mut cache = PlayerInfoCache::new(PLAYER_INFO_CACHE_CAPACITY);
+    for (player_id, entry) in entries {
+        cache.insert(player_id, entry);
+    }
+    cache
+}
+
+fn save_player_cache(cache: &PlayerInfoCache) {
+    let path = player_cache_path();
+    match serde_json::to_string_pretty(cache.entries()) {
+        Ok(json) => {
+            if let Err(x) = fs::write(&path, json) {
+                warn!("Could not write player cache to {:?}: {}", path, x);
+            }
+        }
+        Err(x) => error!("Could not serialize player cache: {}", x),
+    }
+}
+
 // TODO: too lazy to make proper debugging print
 #[derive(Debug)]
 pub enum FetchUpdateStatus {
@@ -19,6 +68,11 @@ pub enum FetchUpdateStatus {
     CannotMatchPlayerID,
     CannotFetchPlayerJS,
     NsigRegexCompileFailed,
+    CannotExtractNsigArray,
+    CannotExtractNsigFunction,
+    CannotExtractSigFunction,
+    CannotExtractHelperObject,
+    CannotParseTimestamp,
     PlayerAlreadyUpdated,
 }
 
@@ -29,13 +83,168 @@ fn player_id_forced() -> u32 {
         return 0;
     }
 
-    u32::from_str_radix(&player_id, 16).unwrap()
+    match u32::from_str_radix(&player_id, 16) {
+        Ok(id) => id,
+        Err(x) => {
+            error!(
+                "Could not parse {}='{}' as hex, falling back to auto-detect: {}",
+                ENV_PLAYER_ID_FORCE, player_id, x
+            );
+            0
+        }
+    }
 }
 
 fn player_id_update_disabled() -> bool {
     std::env::var(ENV_PLAYER_ID_UPDATE_DISABLED).unwrap_or_else(|_| "0".to_string()) == "1"
 }
 
+// How many distinct players to keep extracted code for at once. Clients can
+// be pinned to an older player via a cached signature_timestamp, so we need
+// more than just the currently-live one around to keep serving them.
+const PLAYER_INFO_CACHE_CAPACITY: usize = 6;
+
+// Small in-memory LRU of extracted player code, keyed by player_id. Unlike
+// the single `player_info` slot, this keeps older players around so clients
+// pinned to them via an older signature_timestamp keep working after a new
+// player_id shows up.
+pub struct PlayerInfoCache {
+    capacity: usize,
+    // ordered oldest-to-most-recently-used
+    entries: Vec<(u32, PlayerCacheEntry)>,
+}
+
+impl PlayerInfoCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn insert(&mut self, player_id: u32, entry: PlayerCacheEntry) {
+        self.entries.retain(|(id, _)| *id != player_id);
+        self.entries.push((player_id, entry));
+        while self.entries.len() > self.capacity {
+            self.entries.remove(0);
+        }
+    }
+
+    pub fn get_by_player_id(&mut self, player_id: u32) -> Option<&PlayerCacheEntry> {
+        let index = self.entries.iter().position(|(id, _)| *id == player_id)?;
+        let item = self.entries.remove(index);
+        self.entries.push(item);
+        self.entries.last().map(|(_, entry)| entry)
+    }
+
+    // Lookup used by signature-decode requests: pick the cached player whose
+    // signature_timestamp matches the one the client was issued under.
+    pub fn get_by_signature_timestamp(&mut self, signature_timestamp: u64) -> Option<&PlayerCacheEntry> {
+        let index = self
+            .entries
+            .iter()
+            .position(|(_, entry)| entry.signature_timestamp == signature_timestamp)?;
+        let item = self.entries.remove(index);
+        self.entries.push(item);
+        self.entries.last().map(|(_, entry)| entry)
+    }
+
+    // Ordered oldest-to-most-recently-used, already bounded to `capacity`.
+    // Used to persist this cache to disk without the disk copy growing past
+    // what we'd ever keep in memory.
+    fn entries(&self) -> &[(u32, PlayerCacheEntry)] {
+        &self.entries
+    }
+}
+
+impl Default for PlayerInfoCache {
+    fn default() -> Self {
+        Self::new(PLAYER_INFO_CACHE_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod player_info_cache_tests {
+    use super::*;
+
+    fn entry(signature_timestamp: u64) -> PlayerCacheEntry {
+        PlayerCacheEntry {
+            nsig_function_code: "function decrypt_nsig(a){return a}".to_string(),
+            sig_function_code: "function decrypt_sig(a){return a}".to_string(),
+            sig_function_name: "decrypt_sig".to_string(),
+            signature_timestamp,
+            player_variant: "web".to_string(),
+        }
+    }
+
+    #[test]
+    fn evicts_least_recently_used_when_over_capacity() {
+        let mut cache = PlayerInfoCache::new(2);
+        cache.insert(1, entry(100));
+        cache.insert(2, entry(200));
+        cache.insert(3, entry(300));
+
+        assert!(cache.get_by_player_id(1).is_none());
+        assert!(cache.get_by_player_id(2).is_some());
+        assert!(cache.get_by_player_id(3).is_some());
+    }
+
+    #[test]
+    fn access_promotes_entry_so_it_survives_eviction() {
+        let mut cache = PlayerInfoCache::new(2);
+        cache.insert(1, entry(100));
+        cache.insert(2, entry(200));
+
+        // Touch player 1 so it becomes the most-recently-used.
+        assert!(cache.get_by_player_id(1).is_some());
+
+        // Inserting a third player should now evict player 2, not player 1.
+        cache.insert(3, entry(300));
+
+        assert!(cache.get_by_player_id(1).is_some());
+        assert!(cache.get_by_player_id(2).is_none());
+        assert!(cache.get_by_player_id(3).is_some());
+    }
+
+    #[test]
+    fn reinserting_an_existing_player_id_updates_it_without_growing() {
+        let mut cache = PlayerInfoCache::new(2);
+        cache.insert(1, entry(100));
+        cache.insert(1, entry(101));
+        assert_eq!(cache.entries().len(), 1);
+
+        let found = cache.get_by_player_id(1).expect("entry should be present");
+        assert_eq!(found.signature_timestamp, 101);
+    }
+
+    #[test]
+    fn looks_up_by_signature_timestamp() {
+        let mut cache = PlayerInfoCache::new(4);
+        cache.insert(1, entry(100));
+        cache.insert(2, entry(200));
+
+        let found = cache
+            .get_by_signature_timestamp(200)
+            .expect("entry with matching timestamp should be found");
+        assert_eq!(found.signature_timestamp, 200);
+
+        assert!(cache.get_by_signature_timestamp(999).is_none());
+    }
+}
+
+// Player JS path templates to try, in order, for a given player_id, served
+// from the same https://www.youtube.com/s/player/{player_id}/ prefix as the
+// regular web player. Deliberately limited to the web and web-embedded
+// clients: both are confirmed to be served under this path layout and to
+// contain the same nsig/sig/helper-object shape our regexes are tuned for.
+// Other clients (tv, ios, ...) ship player JS under different, unconfirmed
+// path layouts — adding them here without verifying the actual URL risks
+// running extraction against an unrelated script and caching wrong code.
+const PLAYER_JS_VARIANTS: &[(&str, &str)] = &[
+    ("web", "player_ias.vflset/en_US/base.js"),
+    ("web_embedded", "player_ias_tce.vflset/en_US/base.js"),
+];
+
 fn extract_player_js_global_var(jscode: &str) -> Option<(String, String, String)> {
     let re = Regex::new(r#"(?x)
         'use\s+strict';\s*
@@ -47,7 +256,7 @@ fn extract_player_js_global_var(jscode: &str) -> Option<(String, String, String)
                 |\[(?:(?:"[^"\\]*(?:\\.[^"\\]*)*"|'[^'\\]*(?:\\.[^'\\]*)*')\s*,?\s*)*\]
             )
         )[;,]"#).ok()?;
-    
+
     if let Some(caps) = re.captures(jscode) {
         Some((
             caps.name("code")?.as_str().to_string(),
@@ -62,14 +271,14 @@ fn extract_player_js_global_var(jscode: &str) -> Option<(String, String, String)
 fn fixup_nsig_jscode(jscode: &str, player_javascript: &str) -> String {
     // First try to extract any global variable
     let mut result = jscode.to_string();
-    
+
     // Extract the original parameter name from the input JavaScript code
     let param_regex = Regex::new(r"function\s+[a-zA-Z0-9_$]+\s*\(([a-zA-Z0-9_$]+)\)").unwrap();
     let param_name = param_regex.captures(jscode)
         .and_then(|caps| caps.get(1))
         .map(|m| m.as_str())
         .unwrap_or("a"); // fallback to 'a' if we can't find the original parameter
-    
+
     let fixup_re = if let Some((global_var, varname, _)) = extract_player_js_global_var(player_javascript) {
         debug!("global_var: {}", global_var);
         debug!("varname: {}", varname);
@@ -98,83 +307,26 @@ fn fixup_nsig_jscode(jscode: &str, player_javascript: &str) -> String {
     result
 }
 
-pub async fn fetch_update(state: Arc<GlobalState>) -> Result<(), FetchUpdateStatus> {
-    let global_state = state.clone();
-    let response = match reqwest::get(TEST_YOUTUBE_VIDEO).await {
-        Ok(req) => req.text().await.unwrap(),
-        Err(x) => {
-            error!("Could not fetch the test video: {}", x);
-            return Err(FetchUpdateStatus::CannotFetchTestVideo);
-        }
-    };
-
-    let player_id: u32 = player_id_forced();
-    if player_id == 0 {
-        let player_id_str = match REGEX_PLAYER_ID.captures(&response).unwrap().get(1) {
-            Some(result) => result.as_str(),
-            None => return Err(FetchUpdateStatus::CannotMatchPlayerID),
-        };
-
-        player_id = u32::from_str_radix(player_id_str, 16).unwrap();
-    } else {
-        info!("Using forced player ID: {}", player_id);
-    }
-
-    let mut current_player_info = global_state.player_info.lock().await;
-    let current_player_id = current_player_info.player_id;
-
-    if (current_player_info.has_player == 0xFF) {
-        if player_id_forced() != 0 {
-            info!("Player ID forced, skipping update");
-            return Ok(());
-        }
-        if player_id_update_disabled() {
-            info!("Player ID update disabled, skipping update");
-            return Ok(());
-        }
-    }
-
-    if player_id == current_player_id {
-        current_player_info.last_update = SystemTime::now();
-        return Err(FetchUpdateStatus::PlayerAlreadyUpdated);
-    }
-    // release the mutex for other tasks
-    drop(current_player_info);
-
-    // we have enough info for ytdlp to decode the signature
-    if ytdlp_requested() {
-        current_player_info = global_state.player_info.lock().await;
-        current_player_info.player_id = player_id;
-        current_player_info.signature_timestamp = ytdlp_signature_timestamp(player_id);
-        current_player_info.has_player = 0xFF;
-        current_player_info.last_update = SystemTime::now();
-        return Ok(());
-    }
-    
-    // Download the player script
-    let player_js_url: String = format!(
-        "https://www.youtube.com/s/player/{:08x}/player_ias.vflset/en_US/base.js",
-        player_id
-    );
-    info!("Fetching player JS URL: {}", player_js_url);
-    let player_javascript = match reqwest::get(player_js_url).await {
-        Ok(req) => req.text().await.unwrap(),
-        Err(x) => {
-            error!("Could not fetch the player JS: {}", x);
-            return Err(FetchUpdateStatus::CannotFetchPlayerJS);
-        }
-    };
-
+// Run the nsig + signature extraction pipeline against one downloaded player
+// JS variant. Returns the extracted functions, or the status explaining why
+// this variant didn't yield usable code.
+fn extract_player_functions(player_javascript: &str) -> Result<PlayerCacheEntry, FetchUpdateStatus> {
     let mut nsig_function_array_opt = None;
     // Extract nsig function array code
     for (index, nsig_function_array_str) in NSIG_FUNCTION_ARRAYS.iter().enumerate() {
-        let nsig_function_array_regex = Regex::new(&nsig_function_array_str).unwrap();
-        nsig_function_array_opt = match nsig_function_array_regex.captures(&player_javascript) {
+        let nsig_function_array_regex = match Regex::new(nsig_function_array_str) {
+            Ok(re) => re,
+            Err(x) => {
+                error!("nsig function array regex compilation failed: {}", x);
+                return Err(FetchUpdateStatus::NsigRegexCompileFailed);
+            }
+        };
+        nsig_function_array_opt = match nsig_function_array_regex.captures(player_javascript) {
             None => {
                 warn!("nsig function array did not work: {}", nsig_function_array_str);
-                if index == NSIG_FUNCTION_ARRAYS.len() {
+                if index == NSIG_FUNCTION_ARRAYS.len() - 1 {
                     error!("!!ERROR!! nsig function array unable to be extracted");
-                    return Err(FetchUpdateStatus::NsigRegexCompileFailed);
+                    return Err(FetchUpdateStatus::CannotExtractNsigArray);
                 }
                 continue;
             }
@@ -185,14 +337,24 @@ pub async fn fetch_update(state: Arc<GlobalState>) -> Result<(), FetchUpdateStat
         break;
     }
 
-    let nsig_function_array = nsig_function_array_opt.unwrap();
-    let nsig_array_name = nsig_function_array.name("nfunc").unwrap().as_str();
-    let nsig_array_value = nsig_function_array
-        .name("idx")
-        .unwrap()
-        .as_str()
-        .parse::<usize>()
-        .unwrap();
+    let nsig_function_array = match nsig_function_array_opt {
+        Some(x) => x,
+        None => return Err(FetchUpdateStatus::CannotExtractNsigArray),
+    };
+    let nsig_array_name = match nsig_function_array.name("nfunc") {
+        Some(m) => m.as_str(),
+        None => return Err(FetchUpdateStatus::CannotExtractNsigArray),
+    };
+    let nsig_array_value = match nsig_function_array.name("idx") {
+        Some(m) => match m.as_str().parse::<usize>() {
+            Ok(v) => v,
+            Err(x) => {
+                error!("Could not parse nsig array index: {}", x);
+                return Err(FetchUpdateStatus::CannotExtractNsigArray);
+            }
+        },
+        None => return Err(FetchUpdateStatus::CannotExtractNsigArray),
+    };
 
     let mut nsig_array_context_regex: String = String::new();
     nsig_array_context_regex += "var ";
@@ -207,17 +369,21 @@ pub async fn fetch_update(state: Arc<GlobalState>) -> Result<(), FetchUpdateStat
         }
     };
 
-    let array_content = nsig_array_context
-        .captures(&player_javascript)
-        .unwrap()
-        .get(1)
-        .unwrap()
-        .as_str()
-        .split(',');
+    let nsig_array_context_captures = match nsig_array_context.captures(player_javascript) {
+        Some(caps) => caps,
+        None => return Err(FetchUpdateStatus::CannotExtractNsigArray),
+    };
+    let array_content = match nsig_array_context_captures.get(1) {
+        Some(m) => m.as_str(),
+        None => return Err(FetchUpdateStatus::CannotExtractNsigArray),
+    };
 
-    let array_values: Vec<&str> = array_content.collect();
+    let array_values: Vec<&str> = array_content.split(',').collect();
 
-    let nsig_function_name = array_values.get(nsig_array_value).unwrap();
+    let nsig_function_name = match array_values.get(nsig_array_value) {
+        Some(name) => *name,
+        None => return Err(FetchUpdateStatus::CannotExtractNsigArray),
+    };
 
     let mut nsig_function_code = String::new();
     nsig_function_code += "function ";
@@ -225,6 +391,7 @@ pub async fn fetch_update(state: Arc<GlobalState>) -> Result<(), FetchUpdateStat
 
     debug!("nsig function name: {}", nsig_function_name);
 
+    let mut nsig_function_body_opt = None;
     // Extract nsig function code
     for (index, ending) in NSIG_FUNCTION_ENDINGS.iter().enumerate() {
         let mut nsig_function_code_regex_str: String = String::new();
@@ -232,75 +399,94 @@ pub async fn fetch_update(state: Arc<GlobalState>) -> Result<(), FetchUpdateStat
         nsig_function_code_regex_str += &nsig_function_name.replace("$", "\\$");
         nsig_function_code_regex_str += ending;
 
-        let nsig_function_code_regex = Regex::new(&nsig_function_code_regex_str).unwrap();
-        nsig_function_code += match nsig_function_code_regex.captures(&player_javascript) {
+        let nsig_function_code_regex = match Regex::new(&nsig_function_code_regex_str) {
+            Ok(re) => re,
+            Err(x) => {
+                error!("nsig function code regex compilation failed: {}", x);
+                return Err(FetchUpdateStatus::NsigRegexCompileFailed);
+            }
+        };
+        nsig_function_body_opt = match nsig_function_code_regex.captures(player_javascript) {
             None => {
                 warn!("nsig function ending did not work: {}", ending);
-                if index == NSIG_FUNCTION_ENDINGS.len() {
+                if index == NSIG_FUNCTION_ENDINGS.len() - 1 {
                     error!("!!ERROR!! nsig function unable to be extracted");
-                    return Err(FetchUpdateStatus::NsigRegexCompileFailed);
+                    return Err(FetchUpdateStatus::CannotExtractNsigFunction);
                 }
 
                 continue;
             }
             Some(i) => {
                 debug!("nsig function ending worked: {}", ending);
-                i.get(1).unwrap().as_str()
+                match i.get(1) {
+                    Some(m) => Some(m.as_str().to_string()),
+                    None => return Err(FetchUpdateStatus::CannotExtractNsigFunction),
+                }
             }
         };
-        nsig_function_code = fixup_nsig_jscode(&nsig_function_code, &player_javascript);
-        debug!("got nsig fn code: {}", nsig_function_code);
         break;
     }
 
+    let nsig_function_body = match nsig_function_body_opt {
+        Some(x) => x,
+        None => return Err(FetchUpdateStatus::CannotExtractNsigFunction),
+    };
+    nsig_function_code += &nsig_function_body;
+    nsig_function_code = fixup_nsig_jscode(&nsig_function_code, player_javascript);
+    debug!("got nsig fn code: {}", nsig_function_code);
+
     // Extract signature function name
-    let sig_function_name = REGEX_SIGNATURE_FUNCTION
-        .captures(&player_javascript)
-        .unwrap()
-        .get(1)
-        .unwrap()
-        .as_str();
+    let sig_function_name = match REGEX_SIGNATURE_FUNCTION.captures(player_javascript).and_then(|c| c.get(1)) {
+        Some(m) => m.as_str(),
+        None => return Err(FetchUpdateStatus::CannotExtractSigFunction),
+    };
 
     let mut sig_function_body_regex_str: String = String::new();
     sig_function_body_regex_str += &sig_function_name.replace("$", "\\$");
     sig_function_body_regex_str += "=function\\([a-zA-Z0-9_]+\\)\\{.+?\\}";
 
-    let sig_function_body_regex = Regex::new(&sig_function_body_regex_str).unwrap();
+    let sig_function_body_regex = match Regex::new(&sig_function_body_regex_str) {
+        Ok(re) => re,
+        Err(x) => {
+            error!("sig function body regex compilation failed: {}", x);
+            return Err(FetchUpdateStatus::NsigRegexCompileFailed);
+        }
+    };
 
-    let sig_function_body = sig_function_body_regex
-        .captures(&player_javascript)
-        .unwrap()
-        .get(0)
-        .unwrap()
-        .as_str();
+    let sig_function_body = match sig_function_body_regex.captures(player_javascript).and_then(|c| c.get(0)) {
+        Some(m) => m.as_str(),
+        None => return Err(FetchUpdateStatus::CannotExtractSigFunction),
+    };
 
     // Get the helper object
-    let helper_object_name = REGEX_HELPER_OBJ_NAME
-        .captures(sig_function_body)
-        .unwrap()
-        .get(1)
-        .unwrap()
-        .as_str();
+    let helper_object_name = match REGEX_HELPER_OBJ_NAME.captures(sig_function_body).and_then(|c| c.get(1)) {
+        Some(m) => m.as_str(),
+        None => return Err(FetchUpdateStatus::CannotExtractHelperObject),
+    };
 
     let mut helper_object_body_regex_str = String::new();
     helper_object_body_regex_str += "(var ";
     helper_object_body_regex_str += &helper_object_name.replace("$", "\\$");
     helper_object_body_regex_str += "=\\{(?:.|\\n)+?\\}\\};)";
 
-    let helper_object_body_regex = Regex::new(&helper_object_body_regex_str).unwrap();
-    let helper_object_body = helper_object_body_regex
-        .captures(&player_javascript)
-        .unwrap()
-        .get(0)
-        .unwrap()
-        .as_str();
+    let helper_object_body_regex = match Regex::new(&helper_object_body_regex_str) {
+        Ok(re) => re,
+        Err(x) => {
+            error!("helper object body regex compilation failed: {}", x);
+            return Err(FetchUpdateStatus::NsigRegexCompileFailed);
+        }
+    };
+    let helper_object_body = match helper_object_body_regex.captures(player_javascript).and_then(|c| c.get(0)) {
+        Some(m) => m.as_str(),
+        None => return Err(FetchUpdateStatus::CannotExtractHelperObject),
+    };
 
     let mut sig_code = String::new();
     sig_code += "var ";
     sig_code += sig_function_name;
     sig_code += ";";
 
-    if let Some((global_var, varname, _)) = extract_player_js_global_var(&player_javascript) {
+    if let Some((global_var, varname, _)) = extract_player_js_global_var(player_javascript) {
         sig_code += &global_var;
         sig_code += ";";
         debug!("fix sig code global var: {}", global_var);
@@ -315,23 +501,288 @@ pub async fn fetch_update(state: Arc<GlobalState>) -> Result<(), FetchUpdateStat
     info!("sig code: {}", sig_code);
 
     // Get signature timestamp
-    let signature_timestamp: u64 = REGEX_SIGNATURE_TIMESTAMP
-        .captures(&player_javascript)
-        .unwrap()
-        .get(1)
-        .unwrap()
-        .as_str()
-        .parse()
-        .unwrap();
+    let signature_timestamp: u64 = match REGEX_SIGNATURE_TIMESTAMP.captures(player_javascript).and_then(|c| c.get(1)) {
+        Some(m) => match m.as_str().parse() {
+            Ok(v) => v,
+            Err(x) => {
+                error!("Could not parse signature timestamp: {}", x);
+                return Err(FetchUpdateStatus::CannotParseTimestamp);
+            }
+        },
+        None => return Err(FetchUpdateStatus::CannotParseTimestamp),
+    };
+
+    Ok(PlayerCacheEntry {
+        nsig_function_code,
+        sig_function_code: sig_code,
+        sig_function_name: sig_function_name.to_string(),
+        signature_timestamp,
+        // filled in by the caller once the winning variant is known
+        player_variant: String::new(),
+    })
+}
+
+pub async fn fetch_update(state: Arc<GlobalState>) -> Result<(), FetchUpdateStatus> {
+    let global_state = state.clone();
+    let response = match reqwest::get(TEST_YOUTUBE_VIDEO).await {
+        Ok(req) => match req.text().await {
+            Ok(text) => text,
+            Err(x) => {
+                error!("Could not read the test video response body: {}", x);
+                return Err(FetchUpdateStatus::CannotFetchTestVideo);
+            }
+        },
+        Err(x) => {
+            error!("Could not fetch the test video: {}", x);
+            return Err(FetchUpdateStatus::CannotFetchTestVideo);
+        }
+    };
+
+    let mut player_id: u32 = player_id_forced();
+    if player_id == 0 {
+        let player_id_captures = match REGEX_PLAYER_ID.captures(&response) {
+            Some(caps) => caps,
+            None => return Err(FetchUpdateStatus::CannotMatchPlayerID),
+        };
+        let player_id_str = match player_id_captures.get(1) {
+            Some(result) => result.as_str(),
+            None => return Err(FetchUpdateStatus::CannotMatchPlayerID),
+        };
+
+        player_id = match u32::from_str_radix(player_id_str, 16) {
+            Ok(id) => id,
+            Err(x) => {
+                error!("Could not parse player ID '{}': {}", player_id_str, x);
+                return Err(FetchUpdateStatus::CannotMatchPlayerID);
+            }
+        };
+    } else {
+        info!("Using forced player ID: {}", player_id);
+    }
+
+    let mut current_player_info = global_state.player_info.lock().await;
+    let current_player_id = current_player_info.player_id;
+
+    if (current_player_info.has_player == 0xFF) {
+        if player_id_forced() != 0 {
+            info!("Player ID forced, skipping update");
+            return Ok(());
+        }
+        if player_id_update_disabled() {
+            info!("Player ID update disabled, skipping update");
+            return Ok(());
+        }
+    }
+
+    if player_id == current_player_id {
+        current_player_info.last_update = SystemTime::now();
+        return Err(FetchUpdateStatus::PlayerAlreadyUpdated);
+    }
+    // release the mutex for other tasks
+    drop(current_player_info);
+
+    // we have enough info for ytdlp to decode the signature. There's no
+    // extracted nsig/sig code in this mode, so it has nothing to contribute
+    // to the player_cache LRU (signature decode requests fall back to ytdlp
+    // directly in this case).
+    if ytdlp_requested() {
+        current_player_info = global_state.player_info.lock().await;
+        current_player_info.player_id = player_id;
+        current_player_info.signature_timestamp = ytdlp_signature_timestamp(player_id);
+        current_player_info.player_variant = "ytdlp".to_string();
+        current_player_info.has_player = 0xFF;
+        current_player_info.last_update = SystemTime::now();
+        return Ok(());
+    }
+
+    let mut disk_player_cache = load_player_cache();
+
+    if let Some(cached) = disk_player_cache.get_by_player_id(player_id).cloned() {
+        if !cached.nsig_function_code.is_empty() && !cached.sig_function_code.is_empty() {
+            info!("Using cached player data for player ID {:08x}", player_id);
+            current_player_info = global_state.player_info.lock().await;
+            current_player_info.player_id = player_id;
+            current_player_info.nsig_function_code = cached.nsig_function_code.clone();
+            current_player_info.sig_function_code = cached.sig_function_code.clone();
+            current_player_info.sig_function_name = cached.sig_function_name.clone();
+            current_player_info.signature_timestamp = cached.signature_timestamp;
+            current_player_info.player_variant = cached.player_variant.clone();
+            current_player_info.has_player = 0xFF;
+            current_player_info.last_update = SystemTime::now();
+            drop(current_player_info);
+
+            global_state.player_cache.lock().await.insert(player_id, cached);
+            return Ok(());
+        }
+    }
+
+    // Try each player client variant in turn, keeping the first one whose
+    // player JS yields a working nsig function and signature function.
+    let mut extraction_result: Option<(&str, PlayerCacheEntry)> = None;
+    let mut last_err = FetchUpdateStatus::CannotFetchPlayerJS;
+    for (variant_name, variant_path) in PLAYER_JS_VARIANTS {
+        let player_js_url: String = format!(
+            "https://www.youtube.com/s/player/{:08x}/{}",
+            player_id, variant_path
+        );
+        info!("Fetching player JS URL ({}): {}", variant_name, player_js_url);
+        let player_javascript = match reqwest::get(&player_js_url).await {
+            Ok(req) => match req.error_for_status() {
+                Ok(req) => match req.text().await {
+                    Ok(text) => text,
+                    Err(x) => {
+                        warn!("Could not read the player JS response body for variant {}: {}", variant_name, x);
+                        last_err = FetchUpdateStatus::CannotFetchPlayerJS;
+                        continue;
+                    }
+                },
+                Err(x) => {
+                    // Variant doesn't exist for this player_id (e.g. 404) —
+                    // skip it cleanly instead of running extraction against
+                    // an error page.
+                    warn!("Player JS variant {} returned an error status: {}", variant_name, x);
+                    last_err = FetchUpdateStatus::CannotFetchPlayerJS;
+                    continue;
+                }
+            },
+            Err(x) => {
+                warn!("Could not fetch the player JS for variant {}: {}", variant_name, x);
+                last_err = FetchUpdateStatus::CannotFetchPlayerJS;
+                continue;
+            }
+        };
+
+        match extract_player_functions(&player_javascript) {
+            Ok(mut entry) => {
+                entry.player_variant = variant_name.to_string();
+                extraction_result = Some((variant_name, entry));
+                break;
+            }
+            Err(status) => {
+                warn!("Player variant {} did not yield usable code: {:?}", variant_name, status);
+                last_err = status;
+            }
+        }
+    }
+
+    let (player_variant, extracted) = match extraction_result {
+        Some(x) => x,
+        None => return Err(last_err),
+    };
+
+    // Reload right before writing back (rather than reusing the cache loaded
+    // before the network/regex work above) to keep the read-modify-write
+    // race window on the cache file as narrow as possible.
+    let mut disk_player_cache = load_player_cache();
+    disk_player_cache.insert(player_id, extracted.clone());
+    save_player_cache(&disk_player_cache);
 
     current_player_info = global_state.player_info.lock().await;
     current_player_info.player_id = player_id;
-    current_player_info.nsig_function_code = nsig_function_code;
-    current_player_info.sig_function_code = sig_code;
-    current_player_info.sig_function_name = sig_function_name.to_string();
-    current_player_info.signature_timestamp = signature_timestamp;
+    current_player_info.nsig_function_code = extracted.nsig_function_code.clone();
+    current_player_info.sig_function_code = extracted.sig_function_code.clone();
+    current_player_info.sig_function_name = extracted.sig_function_name.clone();
+    current_player_info.signature_timestamp = extracted.signature_timestamp;
+    current_player_info.player_variant = player_variant.to_string();
     current_player_info.has_player = 0xFF;
     current_player_info.last_update = SystemTime::now();
+    drop(current_player_info);
+
+    global_state.player_cache.lock().await.insert(player_id, extracted);
 
     Ok(())
 }
+
+// Used by signature-decode requests to pick the cached player matching the
+// signature_timestamp a client was issued under, rather than always decoding
+// against the currently-live player. Falls back to the on-disk cache when the
+// in-memory LRU hasn't seen that player yet (e.g. right after a restart, for
+// a player a client is still pinned to), seeding the LRU on a hit so the
+// fallback only pays the disk read once per player.
+pub async fn player_by_signature_timestamp(
+    state: &GlobalState,
+    signature_timestamp: u64,
+) -> Option<PlayerCacheEntry> {
+    let mut player_cache = state.player_cache.lock().await;
+    if let Some(entry) = player_cache.get_by_signature_timestamp(signature_timestamp) {
+        return Some(entry.clone());
+    }
+
+    let disk_player_cache = load_player_cache();
+    let (player_id, entry) = disk_player_cache
+        .entries()
+        .iter()
+        .find(|(_, entry)| entry.signature_timestamp == signature_timestamp)
+        .map(|(player_id, entry)| (*player_id, entry.clone()))?;
+
+    player_cache.insert(player_id, entry.clone());
+    Some(entry)
+}
+
+#[cfg(test)]
+mod disk_cache_tests {
+    use super::*;
+
+    fn with_temp_cache_path<F: FnOnce(&std::path::Path)>(f: F) {
+        let path = std::env::temp_dir().join(format!(
+            "inv_sig_helper_player_cache_test_{:?}.json",
+            std::thread::current().id()
+        ));
+        std::env::set_var(ENV_PLAYER_CACHE_PATH, &path);
+        f(&path);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    fn entry(signature_timestamp: u64) -> PlayerCacheEntry {
+        PlayerCacheEntry {
+            nsig_function_code: "function decrypt_nsig(a){return a}".to_string(),
+            sig_function_code: "function decrypt_sig(a){return a}".to_string(),
+            sig_function_name: "decrypt_sig".to_string(),
+            signature_timestamp,
+            player_variant: "web".to_string(),
+        }
+    }
+
+    #[test]
+    fn round_trips_through_disk() {
+        with_temp_cache_path(|_path| {
+            let mut cache = PlayerInfoCache::new(PLAYER_INFO_CACHE_CAPACITY);
+            cache.insert(0xabcd1234, entry(19834));
+
+            save_player_cache(&cache);
+            let mut loaded = load_player_cache();
+
+            let loaded_entry = loaded
+                .get_by_player_id(0xabcd1234)
+                .expect("entry should round-trip");
+            assert_eq!(loaded_entry.nsig_function_code, entry(19834).nsig_function_code);
+            assert_eq!(loaded_entry.signature_timestamp, 19834);
+            assert_eq!(loaded_entry.player_variant, "web");
+        });
+    }
+
+    #[test]
+    fn missing_file_yields_empty_cache() {
+        with_temp_cache_path(|path| {
+            let _ = std::fs::remove_file(path);
+            assert!(load_player_cache().entries().is_empty());
+        });
+    }
+
+    #[test]
+    fn prunes_to_capacity_before_writing_to_disk() {
+        with_temp_cache_path(|_path| {
+            let mut cache = PlayerInfoCache::new(2);
+            cache.insert(1, entry(100));
+            cache.insert(2, entry(200));
+            cache.insert(3, entry(300));
+
+            save_player_cache(&cache);
+            let mut loaded = load_player_cache();
+
+            assert!(loaded.get_by_player_id(1).is_none());
+            assert!(loaded.get_by_player_id(2).is_some());
+            assert!(loaded.get_by_player_id(3).is_some());
+        });
+    }
+}